@@ -3,6 +3,7 @@ use colored::*;
 use csv::{ReaderBuilder, Writer};
 use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use std::error::Error;
 use std::path::PathBuf;
 use unicode_segmentation::UnicodeSegmentation;
@@ -29,6 +30,115 @@ struct SimilarityScore {
     score: i32,
 }
 
+/// ClusterAssignment groups a contact into a deduplication cluster
+#[derive(Debug, Serialize)]
+struct ClusterAssignment {
+    contact_id: i32,
+    cluster_id: i32,
+}
+
+/// Output format produced on `--output`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum OutputMode {
+    /// One row per above-threshold pair (ContactID1, ContactID2, SimilarityScore)
+    Pairs,
+    /// Connected components over above-threshold pairs, one row per contact (ContactID, ClusterID)
+    Clusters,
+}
+
+/// Selectable string similarity metric
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+#[clap(rename_all = "snake_case")]
+enum Metric {
+    Levenshtein,
+    JaroWinkler,
+    Jaccard,
+    DamerauOsa,
+    Weighted,
+}
+
+/// Per-field weight multipliers used by `calculate_match_score`
+///
+/// Defaults match the tool's original fixed weights (2/2/3/2/2).
+#[derive(Debug, Clone)]
+struct Weights {
+    name: f64,
+    last_name: f64,
+    email: f64,
+    zip_code: f64,
+    address: f64,
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Weights {
+            name: 2.0,
+            last_name: 2.0,
+            email: 3.0,
+            zip_code: 2.0,
+            address: 2.0,
+        }
+    }
+}
+
+impl Weights {
+    /// Sum of all active weights, used to normalize scores to the 0-1000 scale
+    fn sum(&self) -> f64 {
+        self.name + self.last_name + self.email + self.zip_code + self.address
+    }
+}
+
+/// Parses a `--weights` value like `name=2,email=3,zip_code=1` into a `Weights`,
+/// starting from the defaults so unmentioned fields keep their usual weight
+fn parse_weights(s: &str) -> Result<Weights, String> {
+    let mut weights = Weights::default();
+    for entry in s.split(',') {
+        let (field, value) = entry
+            .split_once('=')
+            .ok_or_else(|| format!("invalid weight entry '{entry}', expected field=value"))?;
+        let value: f64 = value
+            .trim()
+            .parse()
+            .map_err(|e| format!("invalid weight value in '{entry}': {e}"))?;
+        match field.trim() {
+            "name" => weights.name = value,
+            "last_name" => weights.last_name = value,
+            "email" => weights.email = value,
+            "zip_code" => weights.zip_code = value,
+            "address" => weights.address = value,
+            other => return Err(format!("unknown weight field '{other}'")),
+        }
+    }
+    Ok(weights)
+}
+
+/// Per-operation costs for the weighted (Sellers) edit distance
+#[derive(Debug, Clone, Copy)]
+struct EditCosts {
+    insert: f64,
+    delete: f64,
+    substitute: f64,
+}
+
+impl Default for EditCosts {
+    fn default() -> Self {
+        EditCosts {
+            insert: 1.0,
+            delete: 1.0,
+            substitute: 1.0,
+        }
+    }
+}
+
+/// Token-based preprocessing applied to multi-word fields before comparison
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum TokenMode {
+    /// Lowercase, dedupe, and sort whitespace-separated tokens before comparing
+    Sort,
+    /// Compare the shared tokens against each string's remaining tokens, keeping the best score
+    Set,
+}
+
 /// CLI argument parser struct using clap
 #[derive(Parser, Debug)]
 #[clap(author, version, about)]
@@ -42,6 +152,41 @@ struct Args {
     // Threshold for similarity score, default is 800
     #[clap(short = 't', long, value_parser = clap::value_parser!(i32).range(0..=1000), default_value = "800")]
     threshold: i32,
+
+    /// Similarity metric applied to every field; defaults to the per-field
+    /// choice (Jaro-Winkler for name/last_name, Levenshtein otherwise)
+    #[clap(long, value_enum)]
+    metric: Option<Metric>,
+
+    /// Per-field weight overrides, e.g. "name=2,email=3,zip_code=1"
+    #[clap(
+        long,
+        value_parser = parse_weights,
+        default_value = "name=2,last_name=2,email=3,zip_code=2,address=2"
+    )]
+    weights: Weights,
+
+    /// Insertion cost used by the `weighted` metric
+    #[clap(long, default_value_t = 1.0)]
+    insert_cost: f64,
+
+    /// Deletion cost used by the `weighted` metric
+    #[clap(long, default_value_t = 1.0)]
+    delete_cost: f64,
+
+    /// Substitution cost used by the `weighted` metric
+    #[clap(long, default_value_t = 1.0)]
+    sub_cost: f64,
+
+    /// Token preprocessing for the name, last_name, and address fields
+    /// (email and zip_code are never token-preprocessed)
+    #[clap(long, value_enum)]
+    token_mode: Option<TokenMode>,
+
+    /// `pairs` writes one row per above-threshold match; `clusters` groups
+    /// transitively matched contacts (A~B, B~C) into cluster IDs instead
+    #[clap(long, value_enum, default_value_t = OutputMode::Pairs)]
+    output_mode: OutputMode,
 }
 
 /// Calculates the Levenshtein distance between two strings
@@ -49,13 +194,66 @@ fn levenshtein_distance(s1: &str, s2: &str) -> usize {
     let s1_chars: Vec<&str> = s1.graphemes(true).collect();
     let s2_chars: Vec<&str> = s2.graphemes(true).collect();
 
+    // Keep the shorter string along the row so it only ever holds
+    // min(len1, len2) + 1 entries, instead of allocating a full matrix
+    let (shorter, longer) = if s1_chars.len() <= s2_chars.len() {
+        (&s1_chars, &s2_chars)
+    } else {
+        (&s2_chars, &s1_chars)
+    };
+    let short_len = shorter.len();
+
+    let mut previous_row: Vec<usize> = (0..=short_len).collect();
+    let mut current_row = vec![0; short_len + 1];
+
+    for (i, &longer_char) in longer.iter().enumerate() {
+        current_row[0] = i + 1;
+        for j in 1..=short_len {
+            let cost = if longer_char == shorter[j - 1] { 0 } else { 1 };
+            current_row[j] = std::cmp::min(
+                std::cmp::min(
+                    previous_row[j] + 1,    // deletion
+                    current_row[j - 1] + 1, // insertion
+                ),
+                previous_row[j - 1] + cost, // substitution
+            );
+        }
+        std::mem::swap(&mut previous_row, &mut current_row);
+    }
+
+    previous_row[short_len]
+}
+
+/// Calculates similarity score between two strings using Levenshtein distance
+/// Time complexity: O(mn) where m and n are the lengths of input strings
+/// Space complexity: O(min(m, n)) thanks to the rolling-row levenshtein_distance
+fn calculate_similarity(s1: &str, s2: &str) -> f64 {
+    if s1.is_empty() && s2.is_empty() {
+        return 1.0;
+    }
+    if s1.is_empty() || s2.is_empty() {
+        return 0.0;
+    }
+    let distance = levenshtein_distance(s1, s2);
+    let max_len = std::cmp::max(s1.chars().count(), s2.chars().count());
+    1.0 - (distance as f64 / max_len as f64)
+}
+
+/// Calculates the Damerau/OSA (optimal string alignment) distance between two strings
+///
+/// Extends Levenshtein with adjacent transpositions as a single edit, so a
+/// swapped pair like "pattern"/"pattren" costs 1 instead of 2. Unlike true
+/// Damerau-Levenshtein, a transposed substring cannot be edited again
+/// afterwards (the "optimal string alignment" restriction).
+fn osa_distance(s1: &str, s2: &str) -> usize {
+    let s1_chars: Vec<&str> = s1.graphemes(true).collect();
+    let s2_chars: Vec<&str> = s2.graphemes(true).collect();
+
     let len1 = s1_chars.len();
     let len2 = s2_chars.len();
 
-    // Initialize matrix for dynamic programming
     let mut matrix = vec![vec![0; len2 + 1]; len1 + 1];
 
-    // Initialize first row and column using iterators
     matrix
         .iter_mut()
         .enumerate()
@@ -68,7 +266,6 @@ fn levenshtein_distance(s1: &str, s2: &str) -> usize {
         .take(len2 + 1)
         .for_each(|(j, cell)| *cell = j);
 
-    // Fill the matrix using dynamic programming
     for i in 1..=len1 {
         for j in 1..=len2 {
             let cost = if s1_chars[i - 1] == s2_chars[j - 1] {
@@ -83,40 +280,376 @@ fn levenshtein_distance(s1: &str, s2: &str) -> usize {
                 ),
                 matrix[i - 1][j - 1] + cost, // substitution
             );
+
+            if i > 1
+                && j > 1
+                && s1_chars[i - 1] == s2_chars[j - 2]
+                && s1_chars[i - 2] == s2_chars[j - 1]
+            {
+                matrix[i][j] = std::cmp::min(matrix[i][j], matrix[i - 2][j - 2] + 1);
+                // transposition
+            }
         }
     }
 
     matrix[len1][len2]
 }
 
-/// Calculates similarity score between two strings using Levenshtein distance
-/// Time complexity: O(mn) where m and n are the lengths of input strings
-/// Space complexity: O(mn) due to levenshtein_distance function
-fn calculate_similarity(s1: &str, s2: &str) -> f64 {
+/// Calculates similarity score between two strings using OSA distance,
+/// normalizing by `max_len` exactly as `calculate_similarity` does
+fn calculate_similarity_osa(s1: &str, s2: &str) -> f64 {
     if s1.is_empty() && s2.is_empty() {
         return 1.0;
     }
     if s1.is_empty() || s2.is_empty() {
         return 0.0;
     }
-    let distance = levenshtein_distance(s1, s2);
+    let distance = osa_distance(s1, s2);
     let max_len = std::cmp::max(s1.chars().count(), s2.chars().count());
     1.0 - (distance as f64 / max_len as f64)
 }
 
+/// Calculates the weighted (Sellers) edit distance between two strings, where
+/// insertion, deletion, and substitution each carry their own cost
+///
+/// This lets callers model asymmetric matching, e.g. making deletions cheap
+/// so "123 Main St" matches "123 Main Street" with a smaller penalty.
+fn weighted_edit_distance(
+    s1: &str,
+    s2: &str,
+    insert_cost: f64,
+    delete_cost: f64,
+    sub_cost: f64,
+) -> f64 {
+    let s1_chars: Vec<&str> = s1.graphemes(true).collect();
+    let s2_chars: Vec<&str> = s2.graphemes(true).collect();
+
+    let len1 = s1_chars.len();
+    let len2 = s2_chars.len();
+
+    let mut matrix = vec![vec![0.0; len2 + 1]; len1 + 1];
+
+    matrix
+        .iter_mut()
+        .enumerate()
+        .take(len1 + 1)
+        .for_each(|(i, row)| row[0] = i as f64 * delete_cost);
+
+    matrix[0]
+        .iter_mut()
+        .enumerate()
+        .take(len2 + 1)
+        .for_each(|(j, cell)| *cell = j as f64 * insert_cost);
+
+    for i in 1..=len1 {
+        for j in 1..=len2 {
+            let cost = if s1_chars[i - 1] == s2_chars[j - 1] {
+                0.0
+            } else {
+                sub_cost
+            };
+            matrix[i][j] = f64::min(
+                f64::min(
+                    matrix[i - 1][j] + delete_cost, // deletion
+                    matrix[i][j - 1] + insert_cost, // insertion
+                ),
+                matrix[i - 1][j - 1] + cost, // substitution
+            );
+        }
+    }
+
+    matrix[len1][len2]
+}
+
+/// Calculates similarity score using the weighted edit distance, normalizing
+/// by the maximum possible cost for the two strings (deleting all of `s1`
+/// and inserting all of `s2`) rather than raw length
+fn calculate_similarity_weighted(
+    s1: &str,
+    s2: &str,
+    insert_cost: f64,
+    delete_cost: f64,
+    sub_cost: f64,
+) -> f64 {
+    let len1 = s1.chars().count();
+    let len2 = s2.chars().count();
+    let max_cost = len1 as f64 * delete_cost + len2 as f64 * insert_cost;
+    if max_cost == 0.0 {
+        return 1.0;
+    }
+    let distance = weighted_edit_distance(s1, s2, insert_cost, delete_cost, sub_cost);
+    1.0 - (distance / max_cost)
+}
+
+/// Calculates the Jaro similarity between two strings, in the range 0.0..=1.0
+///
+/// Two characters are considered matching if they are equal and within
+/// `floor(max(len1, len2) / 2) - 1` positions of each other. `t` is half the
+/// number of transpositions among the matched characters.
+fn jaro_similarity(s1: &str, s2: &str) -> f64 {
+    let s1_chars: Vec<&str> = s1.graphemes(true).collect();
+    let s2_chars: Vec<&str> = s2.graphemes(true).collect();
+    let len1 = s1_chars.len();
+    let len2 = s2_chars.len();
+
+    if len1 == 0 && len2 == 0 {
+        return 1.0;
+    }
+    if len1 == 0 || len2 == 0 {
+        return 0.0;
+    }
+
+    let match_distance = (std::cmp::max(len1, len2) / 2).saturating_sub(1);
+
+    let mut s1_matches = vec![false; len1];
+    let mut s2_matches = vec![false; len2];
+    let mut matches = 0;
+
+    for i in 0..len1 {
+        let start = i.saturating_sub(match_distance);
+        let end = std::cmp::min(i + match_distance + 1, len2);
+        for j in start..end {
+            if s2_matches[j] || s1_chars[i] != s2_chars[j] {
+                continue;
+            }
+            s1_matches[i] = true;
+            s2_matches[j] = true;
+            matches += 1;
+            break;
+        }
+    }
+
+    if matches == 0 {
+        return 0.0;
+    }
+
+    let mut transpositions = 0;
+    let mut k = 0;
+    for i in 0..len1 {
+        if !s1_matches[i] {
+            continue;
+        }
+        while !s2_matches[k] {
+            k += 1;
+        }
+        if s1_chars[i] != s2_chars[k] {
+            transpositions += 1;
+        }
+        k += 1;
+    }
+
+    let t = transpositions as f64 / 2.0;
+    let m = matches as f64;
+
+    (m / len1 as f64 + m / len2 as f64 + (m - t) / m) / 3.0
+}
+
+/// Calculates the Jaro-Winkler similarity between two strings, in the range 0.0..=1.0
+///
+/// Boosts the Jaro similarity by the length of the common prefix (capped at
+/// 4 graphemes), which rewards the typos that show up later in a name rather
+/// than at the start.
+fn jaro_winkler_similarity(s1: &str, s2: &str) -> f64 {
+    let jaro = jaro_similarity(s1, s2);
+
+    let s1_chars: Vec<&str> = s1.graphemes(true).collect();
+    let s2_chars: Vec<&str> = s2.graphemes(true).collect();
+
+    let prefix_len = s1_chars
+        .iter()
+        .zip(s2_chars.iter())
+        .take(4)
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let scaling = 0.1;
+    jaro + (prefix_len as f64 * scaling * (1.0 - jaro))
+}
+
+/// Calculates the Jaccard similarity between two strings using character bigrams
+///
+/// Single-grapheme strings fall back to comparing their one "bigram" (the
+/// string itself) so short fields like zip codes still produce a sensible score.
+fn jaccard_similarity(s1: &str, s2: &str) -> f64 {
+    if s1.is_empty() && s2.is_empty() {
+        return 1.0;
+    }
+    if s1.is_empty() || s2.is_empty() {
+        return 0.0;
+    }
+
+    let bigrams = |s: &str| -> HashSet<String> {
+        let chars: Vec<&str> = s.graphemes(true).collect();
+        if chars.len() < 2 {
+            return HashSet::from([chars.concat()]);
+        }
+        chars.windows(2).map(|pair| pair.concat()).collect()
+    };
+
+    let set1 = bigrams(s1);
+    let set2 = bigrams(s2);
+    let intersection = set1.intersection(&set2).count();
+    let union = set1.union(&set2).count();
+    intersection as f64 / union as f64
+}
+
+/// Computes similarity between two strings using the given metric
+fn similarity_by_metric(metric: Metric, s1: &str, s2: &str, edit_costs: &EditCosts) -> f64 {
+    match metric {
+        Metric::Levenshtein => calculate_similarity(s1, s2),
+        Metric::JaroWinkler => jaro_winkler_similarity(s1, s2),
+        Metric::Jaccard => jaccard_similarity(s1, s2),
+        Metric::DamerauOsa => calculate_similarity_osa(s1, s2),
+        Metric::Weighted => calculate_similarity_weighted(
+            s1,
+            s2,
+            edit_costs.insert,
+            edit_costs.delete,
+            edit_costs.substitute,
+        ),
+    }
+}
+
+/// Lowercases a field and splits it into whitespace-separated tokens
+fn normalize_tokens(s: &str) -> Vec<String> {
+    s.split_whitespace()
+        .map(|token| token.to_lowercase())
+        .collect()
+}
+
+/// Builds the "token sort" key: lowercase, dedupe, and sort the field's
+/// tokens, then rejoin with a single space, so word order stops mattering
+fn token_sort_key(s: &str) -> String {
+    let mut tokens = normalize_tokens(s);
+    tokens.sort();
+    tokens.dedup();
+    tokens.join(" ")
+}
+
+/// Computes "token set" similarity: the tokens shared by both strings are
+/// compared against each string's own tokens plus its remaining, unshared
+/// tokens, and the best of the three resulting scores is kept. This makes
+/// extra or missing tokens much less punishing than a raw string compare.
+fn token_set_similarity(metric: Metric, s1: &str, s2: &str, edit_costs: &EditCosts) -> f64 {
+    let tokens1: HashSet<String> = normalize_tokens(s1).into_iter().collect();
+    let tokens2: HashSet<String> = normalize_tokens(s2).into_iter().collect();
+
+    let mut intersection: Vec<&String> = tokens1.intersection(&tokens2).collect();
+    intersection.sort();
+    let mut remainder1: Vec<&String> = tokens1.difference(&tokens2).collect();
+    remainder1.sort();
+    let mut remainder2: Vec<&String> = tokens2.difference(&tokens1).collect();
+    remainder2.sort();
+
+    let join = |tokens: &[&String]| -> String {
+        tokens
+            .iter()
+            .map(|token| token.as_str())
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    let combine = |base: &str, rest: &str| -> String {
+        if base.is_empty() {
+            rest.to_string()
+        } else if rest.is_empty() {
+            base.to_string()
+        } else {
+            format!("{base} {rest}")
+        }
+    };
+
+    let shared = join(&intersection);
+    let combined1 = combine(&shared, &join(&remainder1));
+    let combined2 = combine(&shared, &join(&remainder2));
+
+    [
+        similarity_by_metric(metric, &shared, &combined1, edit_costs),
+        similarity_by_metric(metric, &shared, &combined2, edit_costs),
+        similarity_by_metric(metric, &combined1, &combined2, edit_costs),
+    ]
+    .into_iter()
+    .fold(0.0, f64::max)
+}
+
+/// Computes similarity for a single field, applying `token_mode` (if any)
+/// before dispatching to the chosen metric
+fn field_similarity(
+    metric: Metric,
+    token_mode: Option<TokenMode>,
+    s1: &str,
+    s2: &str,
+    edit_costs: &EditCosts,
+) -> f64 {
+    if s1.is_empty() && s2.is_empty() {
+        return 1.0;
+    }
+    if s1.is_empty() || s2.is_empty() {
+        return 0.0;
+    }
+    match token_mode {
+        None => similarity_by_metric(metric, s1, s2, edit_costs),
+        Some(TokenMode::Sort) => {
+            similarity_by_metric(metric, &token_sort_key(s1), &token_sort_key(s2), edit_costs)
+        }
+        Some(TokenMode::Set) => token_set_similarity(metric, s1, s2, edit_costs),
+    }
+}
+
 /// Calculates match score between two contacts
-fn calculate_match_score(contact1: &Contact, contact2: &Contact) -> i32 {
+///
+/// `metric` overrides the similarity metric for every field when set;
+/// otherwise name/last_name use Jaro-Winkler (more forgiving of short
+/// variations like "Jon"/"John") while the remaining fields use Levenshtein.
+/// `token_mode` reorders/dedupes multi-word name, last_name, and address
+/// fields before comparison; email and zip_code are compared as-is, without
+/// token preprocessing.
+/// The score is normalized to the 0-1000 scale by the sum of `weights`.
+fn calculate_match_score(
+    contact1: &Contact,
+    contact2: &Contact,
+    metric: Option<Metric>,
+    weights: &Weights,
+    edit_costs: &EditCosts,
+    token_mode: Option<TokenMode>,
+) -> i32 {
+    let name_metric = metric.unwrap_or(Metric::JaroWinkler);
+    let field_metric = metric.unwrap_or(Metric::Levenshtein);
+
     let mut score = 0.0;
 
     // Weight multipliers for different fields
-    score += calculate_similarity(&contact1.name, &contact2.name) * 2.0;
-    score += calculate_similarity(&contact1.last_name, &contact2.last_name) * 2.0;
-    score += calculate_similarity(&contact1.email, &contact2.email) * 3.0;
-    score += calculate_similarity(&contact1.zip_code, &contact2.zip_code) * 2.0;
-    score += calculate_similarity(&contact1.address, &contact2.address) * 2.0;
+    score += field_similarity(
+        name_metric,
+        token_mode,
+        &contact1.name,
+        &contact2.name,
+        edit_costs,
+    ) * weights.name;
+    score += field_similarity(
+        name_metric,
+        token_mode,
+        &contact1.last_name,
+        &contact2.last_name,
+        edit_costs,
+    ) * weights.last_name;
+    score += similarity_by_metric(field_metric, &contact1.email, &contact2.email, edit_costs)
+        * weights.email;
+    score += similarity_by_metric(
+        field_metric,
+        &contact1.zip_code,
+        &contact2.zip_code,
+        edit_costs,
+    ) * weights.zip_code;
+    score += field_similarity(
+        field_metric,
+        token_mode,
+        &contact1.address,
+        &contact2.address,
+        edit_costs,
+    ) * weights.address;
 
     // Normalize to 0-1000 scale
-    ((score * 1000.0) / 11.0) as i32
+    ((score * 1000.0) / weights.sum()) as i32
 }
 
 /// Reads contacts from CSV file
@@ -147,8 +680,104 @@ fn write_scores_to_csv(path: &PathBuf, scores: &[SimilarityScore]) -> Result<(),
     Ok(())
 }
 
+/// Writes cluster assignments to CSV file
+fn write_clusters_to_csv(
+    path: &PathBuf,
+    clusters: &[ClusterAssignment],
+) -> Result<(), Box<dyn Error>> {
+    let mut wtr = Writer::from_path(path)?;
+
+    // Write headers
+    wtr.write_record(["ContactID", "ClusterID"])?;
+
+    // Write data
+    for cluster in clusters {
+        wtr.write_record(&[
+            cluster.contact_id.to_string(),
+            cluster.cluster_id.to_string(),
+        ])?;
+    }
+
+    wtr.flush()?;
+    Ok(())
+}
+
+/// Disjoint-set (union-find) structure with path compression, used to group
+/// contacts into connected components of above-threshold matches
+struct UnionFind {
+    parent: Vec<usize>,
+}
+
+impl UnionFind {
+    fn new(size: usize) -> Self {
+        UnionFind {
+            parent: (0..size).collect(),
+        }
+    }
+
+    fn find(&mut self, x: usize) -> usize {
+        if self.parent[x] != x {
+            self.parent[x] = self.find(self.parent[x]);
+        }
+        self.parent[x]
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let root_a = self.find(a);
+        let root_b = self.find(b);
+        if root_a != root_b {
+            self.parent[root_a] = root_b;
+        }
+    }
+}
+
+/// Groups contacts into clusters of transitive matches
+///
+/// Each above-threshold pair in `scores` is treated as an edge; connected
+/// components become clusters. Contacts with no matches form their own
+/// singleton cluster. Cluster IDs are assigned in order of first appearance.
+fn compute_clusters(contacts: &[Contact], scores: &[SimilarityScore]) -> Vec<ClusterAssignment> {
+    let index_by_id: HashMap<i32, usize> = contacts
+        .iter()
+        .enumerate()
+        .map(|(i, contact)| (contact.id, i))
+        .collect();
+
+    let mut uf = UnionFind::new(contacts.len());
+    for score in scores {
+        if let (Some(&i1), Some(&i2)) = (
+            index_by_id.get(&score.contact_id1),
+            index_by_id.get(&score.contact_id2),
+        ) {
+            uf.union(i1, i2);
+        }
+    }
+
+    let mut cluster_ids: HashMap<usize, i32> = HashMap::new();
+    contacts
+        .iter()
+        .enumerate()
+        .map(|(i, contact)| {
+            let root = uf.find(i);
+            let next_id = cluster_ids.len() as i32;
+            let cluster_id = *cluster_ids.entry(root).or_insert(next_id);
+            ClusterAssignment {
+                contact_id: contact.id,
+                cluster_id,
+            }
+        })
+        .collect()
+}
+
 /// Calculates similarity scores for all contact pairs in parallel
-fn calculate_similarity_scores(contacts: &[Contact], threshold: i32) -> Vec<SimilarityScore> {
+fn calculate_similarity_scores(
+    contacts: &[Contact],
+    threshold: i32,
+    metric: Option<Metric>,
+    weights: &Weights,
+    edit_costs: &EditCosts,
+    token_mode: Option<TokenMode>,
+) -> Vec<SimilarityScore> {
     // Generate all possible pairs of contacts
     let pairs: Vec<(&Contact, &Contact)> = contacts
         .iter()
@@ -160,7 +789,8 @@ fn calculate_similarity_scores(contacts: &[Contact], threshold: i32) -> Vec<Simi
     pairs
         .par_iter() // Use rayon for parallel processing
         .filter_map(|&(contact1, contact2)| {
-            let score = calculate_match_score(contact1, contact2);
+            let score =
+                calculate_match_score(contact1, contact2, metric, weights, edit_costs, token_mode);
             if score >= threshold {
                 Some(SimilarityScore {
                     contact_id1: contact1.id,
@@ -194,20 +824,54 @@ fn main() -> Result<(), Box<dyn Error>> {
     let contacts = read_contacts_from_csv(&args.file)?;
 
     // Calculate similarity scores with threshold
-    let scores = calculate_similarity_scores(&contacts, args.threshold);
-
-    // Write results to output CSV file
-    write_scores_to_csv(&args.output, &scores)?;
-
-    println!(
-        "{} {} {} {}. {} {}",
-        "Found".bright_white(),
-        scores.len().to_string().bright_green().bold(),
-        "matches above threshold".bright_white(),
-        args.threshold.to_string().yellow().bold(),
-        "Results written to".bright_white(),
-        args.output.to_string_lossy().bright_blue().underline()
+    let edit_costs = EditCosts {
+        insert: args.insert_cost,
+        delete: args.delete_cost,
+        substitute: args.sub_cost,
+    };
+    let scores = calculate_similarity_scores(
+        &contacts,
+        args.threshold,
+        args.metric,
+        &args.weights,
+        &edit_costs,
+        args.token_mode,
     );
+
+    // Write results to output CSV file in the requested format
+    match args.output_mode {
+        OutputMode::Pairs => {
+            write_scores_to_csv(&args.output, &scores)?;
+            println!(
+                "{} {} {} {}. {} {}",
+                "Found".bright_white(),
+                scores.len().to_string().bright_green().bold(),
+                "matches above threshold".bright_white(),
+                args.threshold.to_string().yellow().bold(),
+                "Results written to".bright_white(),
+                args.output.to_string_lossy().bright_blue().underline()
+            );
+        }
+        OutputMode::Clusters => {
+            let clusters = compute_clusters(&contacts, &scores);
+            let cluster_count = clusters
+                .iter()
+                .map(|c| c.cluster_id)
+                .collect::<HashSet<_>>()
+                .len();
+            write_clusters_to_csv(&args.output, &clusters)?;
+            println!(
+                "{} {} {} {} {}. {} {}",
+                "Grouped".bright_white(),
+                contacts.len().to_string().bright_green().bold(),
+                "contacts into".bright_white(),
+                cluster_count.to_string().bright_green().bold(),
+                "clusters".bright_white(),
+                "Results written to".bright_white(),
+                args.output.to_string_lossy().bright_blue().underline()
+            );
+        }
+    }
     Ok(())
 }
 
@@ -258,6 +922,32 @@ mod tests {
         assert_eq!(levenshtein_distance("a", "abcdef"), 5);
         assert_eq!(levenshtein_distance("abcdef", "a"), 5);
     }
+
+    #[test]
+    fn test_osa_distance() {
+        // An adjacent transposition costs a single edit, unlike Levenshtein
+        assert_eq!(osa_distance("pattern", "pattren"), 1);
+        assert_eq!(levenshtein_distance("pattern", "pattren"), 2);
+
+        assert_eq!(osa_distance("", ""), 0);
+        assert_eq!(osa_distance("abc", ""), 3);
+        assert_eq!(osa_distance("ab", "ba"), 1);
+        assert_eq!(osa_distance("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn test_calculate_similarity_osa() {
+        assert_eq!(calculate_similarity_osa("", ""), 1.0);
+        assert_eq!(calculate_similarity_osa("abc", ""), 0.0);
+        assert_eq!(calculate_similarity_osa("abc", "abc"), 1.0);
+
+        // Transposition-only differences score higher than under Levenshtein
+        assert!(
+            calculate_similarity_osa("pattern", "pattren")
+                > calculate_similarity("pattern", "pattren")
+        );
+    }
+
     #[test]
     fn test_calculate_similarity_edge_cases() {
         // Empty strings
@@ -279,6 +969,178 @@ mod tests {
         assert!(calculate_similarity("kitten", "sitting") < 1.0);
     }
 
+    #[test]
+    fn test_jaro_similarity() {
+        assert_eq!(jaro_similarity("", ""), 1.0);
+        assert_eq!(jaro_similarity("abc", ""), 0.0);
+        assert_eq!(jaro_similarity("", "abc"), 0.0);
+        assert_eq!(jaro_similarity("John", "John"), 1.0);
+
+        // Classic reference values for Jaro similarity
+        assert!((jaro_similarity("MARTHA", "MARHTA") - 0.9444444444444445).abs() < 1e-9);
+        assert!((jaro_similarity("DIXON", "DICKSONX") - 0.7666666666666667).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_jaro_winkler_similarity() {
+        assert_eq!(jaro_winkler_similarity("", ""), 1.0);
+        assert_eq!(jaro_winkler_similarity("abc", ""), 0.0);
+        assert_eq!(jaro_winkler_similarity("John", "John"), 1.0);
+
+        // The common-prefix boost should never lower the plain Jaro score
+        assert!(jaro_winkler_similarity("John", "Jon") >= jaro_similarity("John", "Jon"));
+
+        // Classic reference values for Jaro-Winkler similarity
+        assert!((jaro_winkler_similarity("MARTHA", "MARHTA") - 0.9611111111111111).abs() < 1e-9);
+        assert!((jaro_winkler_similarity("DIXON", "DICKSONX") - 0.8133333333333332).abs() < 1e-9);
+
+        // Unicode characters stay grapheme-aware
+        assert!(jaro_winkler_similarity("José", "Jose") > 0.8);
+    }
+
+    #[test]
+    fn test_jaccard_similarity() {
+        assert_eq!(jaccard_similarity("", ""), 1.0);
+        assert_eq!(jaccard_similarity("abc", ""), 0.0);
+        assert_eq!(jaccard_similarity("abc", "abc"), 1.0);
+
+        // "night"/"nacht" share the bigram "ht"
+        assert!(jaccard_similarity("night", "nacht") > 0.0);
+        assert!(jaccard_similarity("completely", "different") < 0.3);
+
+        // Single-grapheme strings fall back to whole-string comparison
+        assert_eq!(jaccard_similarity("a", "a"), 1.0);
+        assert_eq!(jaccard_similarity("a", "b"), 0.0);
+    }
+
+    #[test]
+    fn test_parse_weights() {
+        let weights = parse_weights("name=5,email=1").unwrap();
+        assert_eq!(weights.name, 5.0);
+        assert_eq!(weights.email, 1.0);
+        // Unmentioned fields keep their default weight
+        assert_eq!(weights.last_name, Weights::default().last_name);
+        assert_eq!(weights.sum(), 5.0 + 2.0 + 1.0 + 2.0 + 2.0);
+
+        assert!(parse_weights("bogus_field=1").is_err());
+        assert!(parse_weights("name").is_err());
+        assert!(parse_weights("name=not_a_number").is_err());
+    }
+
+    #[test]
+    fn test_cli_enum_flags_parse_snake_case() {
+        // Regression test: clap's default ValueEnum rendering is kebab-case,
+        // but this CLI documents (and `--weights` already uses) snake_case
+        // values like `jaro_winkler` and `damerau_osa`.
+        let args = Args::try_parse_from([
+            "duplexscan",
+            "-f",
+            "in.csv",
+            "-o",
+            "out.csv",
+            "--metric",
+            "jaro_winkler",
+            "--token-mode",
+            "set",
+            "--output-mode",
+            "clusters",
+        ])
+        .unwrap();
+        assert_eq!(args.metric, Some(Metric::JaroWinkler));
+        assert_eq!(args.token_mode, Some(TokenMode::Set));
+        assert_eq!(args.output_mode, OutputMode::Clusters);
+
+        let args = Args::try_parse_from([
+            "duplexscan",
+            "-f",
+            "in.csv",
+            "-o",
+            "out.csv",
+            "--metric",
+            "damerau_osa",
+        ])
+        .unwrap();
+        assert_eq!(args.metric, Some(Metric::DamerauOsa));
+
+        // The old kebab-case rendering must be rejected, not silently accepted
+        assert!(Args::try_parse_from([
+            "duplexscan",
+            "-f",
+            "in.csv",
+            "-o",
+            "out.csv",
+            "--metric",
+            "jaro-winkler",
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_calculate_match_score_metric_override() {
+        let contact1 =
+            create_test_contact(1, "John", "Doe", "john@example.com", "12345", "123 Main St");
+        let contact2 = create_test_contact(
+            2,
+            "Jon",
+            "Doe",
+            "john@example.com",
+            "12345",
+            "123 Main Street",
+        );
+
+        let default_score = calculate_match_score(
+            &contact1,
+            &contact2,
+            None,
+            &Weights::default(),
+            &EditCosts::default(),
+            None,
+        );
+        let levenshtein_score = calculate_match_score(
+            &contact1,
+            &contact2,
+            Some(Metric::Levenshtein),
+            &Weights::default(),
+            &EditCosts::default(),
+            None,
+        );
+        // Forcing Levenshtein everywhere should score the name difference
+        // more harshly than the default, which favors Jaro-Winkler for names
+        assert!(levenshtein_score < default_score);
+    }
+
+    #[test]
+    fn test_weighted_edit_distance() {
+        assert_eq!(weighted_edit_distance("", "", 1.0, 1.0, 1.0), 0.0);
+        assert_eq!(weighted_edit_distance("abc", "abc", 1.0, 1.0, 1.0), 0.0);
+        // Uniform costs match plain Levenshtein
+        assert_eq!(
+            weighted_edit_distance("kitten", "sitting", 1.0, 1.0, 1.0),
+            levenshtein_distance("kitten", "sitting") as f64
+        );
+
+        // Cheap deletions make a trailing suffix almost free to drop
+        let cheap_delete = weighted_edit_distance("123 Main Street", "123 Main St", 1.0, 0.1, 1.0);
+        let default_cost = weighted_edit_distance("123 Main Street", "123 Main St", 1.0, 1.0, 1.0);
+        assert!(cheap_delete < default_cost);
+    }
+
+    #[test]
+    fn test_calculate_similarity_weighted() {
+        assert_eq!(calculate_similarity_weighted("", "", 1.0, 1.0, 1.0), 1.0);
+        assert_eq!(calculate_similarity_weighted("abc", "", 1.0, 1.0, 1.0), 0.0);
+        assert_eq!(
+            calculate_similarity_weighted("abc", "abc", 1.0, 1.0, 1.0),
+            1.0
+        );
+
+        // Cheap deletions should raise the similarity of a dropped suffix
+        let cheap = calculate_similarity_weighted("123 Main Street", "123 Main St", 1.0, 0.1, 1.0);
+        let default_cost =
+            calculate_similarity_weighted("123 Main Street", "123 Main St", 1.0, 1.0, 1.0);
+        assert!(cheap > default_cost);
+    }
+
     #[test]
     fn test_calculate_match_score() {
         let contact1 =
@@ -286,7 +1148,14 @@ mod tests {
         let mut contact2 = contact1.clone();
         contact2.id = 2;
 
-        let score = calculate_match_score(&contact1, &contact2);
+        let score = calculate_match_score(
+            &contact1,
+            &contact2,
+            None,
+            &Weights::default(),
+            &EditCosts::default(),
+            None,
+        );
         assert_eq!(score, 1000); // Perfect match should give maximum score
     }
     #[test]
@@ -302,8 +1171,15 @@ mod tests {
             "123 Main Street",
         );
 
-        let score = calculate_match_score(&contact1, &contact2);
-        assert_eq!(score, 906); // Similar contacts should have high score
+        let score = calculate_match_score(
+            &contact1,
+            &contact2,
+            None,
+            &Weights::default(),
+            &EditCosts::default(),
+            None,
+        );
+        assert_eq!(score, 939); // Similar contacts should have high score
     }
 
     #[test]
@@ -319,8 +1195,15 @@ mod tests {
             "456 Oak Ave",
         );
 
-        let score = calculate_match_score(&contact1, &contact2);
-        assert_eq!(score, 336); // Different contacts should have lower score
+        let score = calculate_match_score(
+            &contact1,
+            &contact2,
+            None,
+            &Weights::default(),
+            &EditCosts::default(),
+            None,
+        );
+        assert_eq!(score, 418); // Different contacts should have lower score
     }
 
     #[test]
@@ -329,24 +1212,38 @@ mod tests {
         let empty_contact1 = create_test_contact(1, "", "", "", "", "");
         let empty_contact2 = create_test_contact(2, "", "", "", "", "");
         assert_eq!(
-            calculate_match_score(&empty_contact1, &empty_contact2),
+            calculate_match_score(
+                &empty_contact1,
+                &empty_contact2,
+                None,
+                &Weights::default(),
+                &EditCosts::default(),
+                None
+            ),
             1000
         );
 
         // One empty contact
         let normal_contact =
             create_test_contact(1, "John", "Doe", "john@example.com", "", "123 Main St");
-        let score = calculate_match_score(&normal_contact, &empty_contact1);
+        let score = calculate_match_score(
+            &normal_contact,
+            &empty_contact1,
+            None,
+            &Weights::default(),
+            &EditCosts::default(),
+            None,
+        );
         assert_eq!(score, 181);
 
         // Unicode characters
         let unicode_contact1 = create_test_contact(
             1,
-            "JosÃ©",
-            "GarcÃ­a",
+            "José",
+            "García",
             "jose@example.com",
             "12345",
-            "123 CafÃ© St",
+            "123 Café St",
         );
         let unicode_contact2 = create_test_contact(
             2,
@@ -356,8 +1253,122 @@ mod tests {
             "12345",
             "123 Cafe St",
         );
-        let unicode_score = calculate_match_score(&unicode_contact1, &unicode_contact2);
+        let unicode_score = calculate_match_score(
+            &unicode_contact1,
+            &unicode_contact2,
+            None,
+            &Weights::default(),
+            &EditCosts::default(),
+            None,
+        );
         println!("Unicode score: {}", unicode_score);
-        assert_eq!(unicode_score, 907); // Should still be high despite accent differences
+        assert_eq!(unicode_score, 950); // Should still be high despite accent differences
+    }
+
+    #[test]
+    fn test_token_sort_key() {
+        assert_eq!(token_sort_key("Jose Maria"), token_sort_key("Maria Jose"));
+        assert_eq!(token_sort_key("John Doe"), "doe john");
+        assert_eq!(token_sort_key(""), "");
+    }
+
+    #[test]
+    fn test_token_set_similarity() {
+        // Same tokens in a different order should match perfectly
+        assert_eq!(
+            token_set_similarity(
+                Metric::JaroWinkler,
+                "Jose Garcia",
+                "Garcia Jose",
+                &EditCosts::default()
+            ),
+            1.0
+        );
+
+        // A subset of tokens should still score highly against the superset
+        let partial = token_set_similarity(
+            Metric::JaroWinkler,
+            "Jose Garcia",
+            "Jose Garcia Jr",
+            &EditCosts::default(),
+        );
+        assert_eq!(partial, 1.0);
+    }
+
+    #[test]
+    fn test_calculate_match_score_token_mode_sort() {
+        let contact1 = create_test_contact(
+            1,
+            "Jose Maria",
+            "Garcia Lopez",
+            "jose@example.com",
+            "12345",
+            "123 Main St",
+        );
+        let contact2 = create_test_contact(
+            2,
+            "Maria Jose",
+            "Garcia Lopez",
+            "jose@example.com",
+            "12345",
+            "123 Main St",
+        );
+
+        let default_score = calculate_match_score(
+            &contact1,
+            &contact2,
+            None,
+            &Weights::default(),
+            &EditCosts::default(),
+            None,
+        );
+        let sorted_score = calculate_match_score(
+            &contact1,
+            &contact2,
+            None,
+            &Weights::default(),
+            &EditCosts::default(),
+            Some(TokenMode::Sort),
+        );
+        // Reordered given names should score a perfect match once tokens are sorted
+        assert!(sorted_score > default_score);
+        assert_eq!(sorted_score, 1000);
+    }
+
+    #[test]
+    fn test_compute_clusters_transitive_matches() {
+        let contacts = vec![
+            create_test_contact(1, "John", "Doe", "a@example.com", "12345", "123 Main St"),
+            create_test_contact(2, "John", "Doe", "a@example.com", "12345", "123 Main St"),
+            create_test_contact(3, "John", "Doe", "a@example.com", "12345", "123 Main St"),
+            create_test_contact(4, "Jane", "Smith", "b@example.com", "54321", "456 Oak Ave"),
+        ];
+        // A~B and B~C should merge into a single cluster even without a direct A~C edge
+        let scores = vec![
+            SimilarityScore {
+                contact_id1: 1,
+                contact_id2: 2,
+                score: 1000,
+            },
+            SimilarityScore {
+                contact_id1: 2,
+                contact_id2: 3,
+                score: 1000,
+            },
+        ];
+
+        let clusters = compute_clusters(&contacts, &scores);
+        let cluster_id = |id: i32| {
+            clusters
+                .iter()
+                .find(|c| c.contact_id == id)
+                .unwrap()
+                .cluster_id
+        };
+
+        assert_eq!(cluster_id(1), cluster_id(2));
+        assert_eq!(cluster_id(2), cluster_id(3));
+        // The unmatched contact should land in its own singleton cluster
+        assert_ne!(cluster_id(1), cluster_id(4));
     }
 }